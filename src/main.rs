@@ -13,6 +13,13 @@ arg_enum!{
     }
 }
 
+arg_enum!{
+    enum Format {
+        Int,
+        Float
+    }
+}
+
 impl Shape {
     fn func(&self) -> (fn(f32) -> f32) {
         match self {
@@ -22,6 +29,36 @@ impl Shape {
             Shape::Triangle => gen_triangle,
         }
     }
+
+    // Band-limited counterpart of `func`, taking the phase increment `dt`
+    // into account to correct for aliasing around discontinuities.
+    fn func_antialiased(&self) -> (fn(f32, f32) -> f32) {
+        match self {
+            Shape::Saw => gen_saw_antialiased,
+            Shape::Sine => gen_sine_antialiased,
+            Shape::Square => gen_square_antialiased,
+            Shape::Triangle => gen_triangle_antialiased,
+        }
+    }
+
+    fn eval(&self, t: f32, dt: f32, antialias: bool) -> f32 {
+        if antialias {
+            self.func_antialiased()(t, dt)
+        } else {
+            self.func()(t)
+        }
+    }
+}
+
+struct Waveform {
+    shape: Shape,
+    antialias: bool,
+}
+
+impl Waveform {
+    fn eval(&self, t: f32, dt: f32) -> f32 {
+        self.shape.eval(t, dt, self.antialias)
+    }
 }
 
 fn gen_sine(x: f32) -> f32 {
@@ -52,6 +89,40 @@ fn gen_triangle(x: f32) -> f32 {
     }
 }
 
+// PolyBLEP (polynomial band-limited step) correction, subtracted from the
+// naive waveform in a small window around each discontinuity to suppress
+// the aliasing that the sharp edge would otherwise introduce.
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let x = t / dt;
+        2.0 * x - x * x - 1.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt;
+        x * x + 2.0 * x + 1.0
+    } else {
+        0.0
+    }
+}
+
+fn gen_sine_antialiased(t: f32, _dt: f32) -> f32 {
+    gen_sine(t)
+}
+
+fn gen_triangle_antialiased(t: f32, _dt: f32) -> f32 {
+    gen_triangle(t)
+}
+
+fn gen_saw_antialiased(t: f32, dt: f32) -> f32 {
+    assert!(t >= 0.0 && t < 1.0);
+    (gen_saw(t) - poly_blep(t, dt)).max(-1.0).min(1.0)
+}
+
+fn gen_square_antialiased(t: f32, dt: f32) -> f32 {
+    assert!(t >= 0.0 && t < 1.0);
+    let t2 = (t + 0.5) % 1.0;
+    (gen_saw_antialiased(t2, dt) - gen_saw_antialiased(t, dt)).max(-1.0).min(1.0)
+}
+
 struct Signal {
     curr_tick: u32,
     last_tick: u32,
@@ -78,7 +149,10 @@ impl Signal {
 }
 
 impl Iterator for Signal {
-    type Item = f32;
+    // Normalized phase `t` in `[0,1)` together with the per-sample phase
+    // increment `dt`, so adapters further down the chain (e.g. PolyBLEP
+    // correction) can tell how fast the phase is moving.
+    type Item = (f32, f32);
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.curr_tick >= self.last_tick {
@@ -89,8 +163,9 @@ impl Iterator for Signal {
             self.ts -= self.sample_rate;
         }
         let t = self.ts / self.sample_rate;
+        let dt = self.freq / self.sample_rate;
         self.ts += self.freq;
-        Some(t)
+        Some((t, dt))
     }
 }
 
@@ -123,71 +198,358 @@ impl Iterator for Silence {
     }
 }
 
-fn adjust_volume(x: f32) -> i16 {
+// Scales `x` in `[-1,1]` to the integer range implied by `spec`'s bit depth,
+// or writes it straight through when `spec` calls for float samples.
+fn write_sample<W: std::io::Write + std::io::Seek>(
+    writer: &mut hound::WavWriter<W>,
+    spec: &hound::WavSpec,
+    x: f32,
+) -> Result<(), hound::Error> {
     assert!(x >= -1.0 && x <= 1.0);
-    let max_ampl = std::i16::MAX as f32;
-    (x * max_ampl) as i16
+    match spec.sample_format {
+        hound::SampleFormat::Float => writer.write_sample(x),
+        hound::SampleFormat::Int => match spec.bits_per_sample {
+            8 => writer.write_sample((x * std::i8::MAX as f32) as i8),
+            16 => writer.write_sample((x * std::i16::MAX as f32) as i16),
+            24 => writer.write_sample((x * 8_388_607.0_f32) as i32),
+            32 => writer.write_sample((x * std::i32::MAX as f32) as i32),
+            bits => panic!("unsupported bit depth: {}", bits),
+        },
+    }
+}
+
+// Writes one frame: a single sample for mono output, or `l`/`r` alternated
+// across however many channels `spec` declares.
+fn write_frame<W: std::io::Write + std::io::Seek>(
+    writer: &mut hound::WavWriter<W>,
+    spec: &hound::WavSpec,
+    l: f32,
+    r: f32,
+) -> Result<(), hound::Error> {
+    if spec.channels == 1 {
+        write_sample(writer, spec, l)
+    } else {
+        for ch in 0..spec.channels {
+            write_sample(writer, spec, if ch % 2 == 0 { l } else { r })?;
+        }
+        Ok(())
+    }
+}
+
+// General-purpose FIR filter, usable as a chained adapter after any
+// oscillator to low-pass/high-pass/shape the signal before it is written out.
+struct FirFilter<I> {
+    inner: I,
+    coeffs: Vec<f32>,
+    state: Vec<f32>,
+    pos: usize,
+}
+
+impl<I> FirFilter<I> {
+    fn new(inner: I, coeffs: Vec<f32>) -> Self {
+        assert!(!coeffs.is_empty());
+        let len = coeffs.len();
+        FirFilter {
+            inner,
+            coeffs,
+            state: vec![0.0; len],
+            pos: 0,
+        }
+    }
+}
+
+impl<I: Iterator<Item = f32>> Iterator for FirFilter<I> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.inner.next()?;
+        let len = self.coeffs.len();
+        self.pos = (self.pos + 1) % len;
+        self.state[self.pos] = sample;
+        let mut out = 0.0;
+        for (i, c) in self.coeffs.iter().enumerate() {
+            out += self.state[(self.pos + len - i) % len] * c;
+        }
+        Some(out)
+    }
+}
+
+// Parses FIR coefficients either from a comma-separated list passed
+// directly on the command line, or from a text file containing
+// comma/whitespace-separated values.
+fn parse_fir_coeffs(spec: &str) -> Vec<f32> {
+    let content = std::fs::read_to_string(spec).unwrap_or_else(|_| spec.to_string());
+    content
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<f32>()
+                .unwrap_or_else(|_| panic!("invalid FIR coefficient: {}", s))
+        }).collect()
+}
+
+fn apply_fir<'a>(
+    iter: impl Iterator<Item = f32> + 'a,
+    coeffs: &Option<Vec<f32>>,
+) -> Box<dyn Iterator<Item = f32> + 'a> {
+    match coeffs {
+        Some(c) => Box::new(FirFilter::new(iter, c.clone())),
+        None => Box::new(iter),
+    }
+}
+
+// Number of taps kept in the resampler's ring buffer; also the support
+// width (in input samples) of the windowed-sinc kernel.
+const RESAMPLER_TAPS: usize = 16;
+
+// Band-limited, arbitrary-rate resampler: a fixed-size ring buffer of the
+// most recent input samples, read at a fractional position advanced by
+// `ratio = input_rate/output_rate` each output step and interpolated with a
+// Hann-windowed sinc kernel.
+struct Resampler<I> {
+    inner: I,
+    ring: [f32; RESAMPLER_TAPS],
+    count: u64,
+    read: f64,
+    ratio: f64,
+    exhausted: bool,
+}
+
+impl<I> Resampler<I> {
+    fn new(inner: I, input_rate: u32, output_rate: u32) -> Self {
+        assert!(output_rate > 0);
+        Resampler {
+            inner,
+            ring: [0.0; RESAMPLER_TAPS],
+            count: 0,
+            read: 0.0,
+            ratio: input_rate as f64 / output_rate as f64,
+            exhausted: false,
+        }
+    }
+
+    fn push(&mut self, sample: f32) {
+        for i in 0..RESAMPLER_TAPS - 1 {
+            self.ring[i] = self.ring[i + 1];
+        }
+        self.ring[RESAMPLER_TAPS - 1] = sample;
+        self.count += 1;
+    }
+
+    // Hann-windowed sinc, zero outside its +/- RESAMPLER_TAPS/2 support.
+    fn kernel(x: f64) -> f64 {
+        let half = RESAMPLER_TAPS as f64 / 2.0;
+        if x.abs() >= half {
+            return 0.0;
+        }
+        let sinc = if x.abs() < 1e-7 {
+            1.0
+        } else {
+            (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x)
+        };
+        let hann = 0.5 * (1.0 + (std::f64::consts::PI * x / half).cos());
+        sinc * hann
+    }
+}
+
+impl<I: Iterator<Item = f32>> Iterator for Resampler<I> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.exhausted && self.count as f64 <= self.read + RESAMPLER_TAPS as f64 / 2.0 {
+            match self.inner.next() {
+                Some(s) => self.push(s),
+                None => self.exhausted = true,
+            }
+        }
+        if self.exhausted && self.count as f64 <= self.read {
+            return None;
+        }
+        let mut acc = 0.0f64;
+        for (k, tap) in self.ring.iter().enumerate() {
+            let idx = self.count as i64 - RESAMPLER_TAPS as i64 + k as i64;
+            if idx < 0 {
+                continue;
+            }
+            acc += *tap as f64 * Self::kernel(self.read - idx as f64);
+        }
+        self.read += self.ratio;
+        Some(acc as f32)
+    }
+}
+
+fn apply_resample<'a>(
+    iter: impl Iterator<Item = f32> + 'a,
+    input_rate: u32,
+    output_rate: Option<u32>,
+) -> Box<dyn Iterator<Item = f32> + 'a> {
+    match output_rate {
+        Some(r) if r != input_rate => Box::new(Resampler::new(iter, input_rate, r)),
+        _ => Box::new(iter),
+    }
+}
+
+// Attack/decay/sustain/release amplitude envelope, applied as an iterator
+// adapter so it composes with the rest of the oscillator/filter chain.
+#[derive(Clone, Copy)]
+struct AdsrParams {
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+}
+
+impl AdsrParams {
+    fn envelope<I>(&self, inner: I, sample_rate: u32, total: u32) -> Adsr<I> {
+        Adsr::new(
+            inner,
+            sample_rate,
+            total,
+            self.attack,
+            self.decay,
+            self.sustain,
+            self.release,
+        )
+    }
+}
+
+struct Adsr<I> {
+    inner: I,
+    n: u32,
+    total: u32,
+    attack: u32,
+    decay: u32,
+    release: u32,
+    sustain: f32,
+}
+
+impl<I> Adsr<I> {
+    fn new(
+        inner: I,
+        sample_rate: u32,
+        total: u32,
+        attack: f32,
+        decay: f32,
+        sustain: f32,
+        release: f32,
+    ) -> Self {
+        assert!(sustain >= 0.0 && sustain <= 1.0);
+        let sr = sample_rate as f32;
+        let attack = std::cmp::min((attack * sr) as u32, total);
+        let decay = std::cmp::min((decay * sr) as u32, total - attack);
+        let release = std::cmp::min((release * sr) as u32, total - attack - decay);
+        Adsr {
+            inner,
+            n: 0,
+            total,
+            attack,
+            decay,
+            release,
+            sustain,
+        }
+    }
+
+    fn value(&self) -> f32 {
+        let n = self.n;
+        if n < self.attack {
+            n as f32 / self.attack as f32
+        } else if n < self.attack + self.decay {
+            let x = (n - self.attack) as f32 / self.decay as f32;
+            1.0 - (1.0 - self.sustain) * x
+        } else if n < self.total - self.release {
+            self.sustain
+        } else if self.release > 0 {
+            let remaining = self.total - n;
+            self.sustain * remaining as f32 / self.release as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+impl<I: Iterator<Item = f32>> Iterator for Adsr<I> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.inner.next()?;
+        let env = self.value();
+        self.n += 1;
+        Some(sample * env)
+    }
+}
+
+struct RenderConfig {
+    rate: u32,
+    filter: Option<Vec<f32>>,
+    adsr: AdsrParams,
+    out_rate: Option<u32>,
+    spec: hound::WavSpec,
+}
+
+impl RenderConfig {
+    fn process<'a>(
+        &self,
+        iter: impl Iterator<Item = f32> + 'a,
+        total: u32,
+    ) -> Box<dyn Iterator<Item = f32> + 'a> {
+        apply_resample(
+            self.adsr.envelope(apply_fir(iter, &self.filter), self.rate, total),
+            self.rate,
+            self.out_rate,
+        )
+    }
 }
 
 fn plain(
     file: &str,
-    rate: u32,
+    cfg: &RenderConfig,
     dur: f32,
     freq: f32,
     phase: f32,
-    shape: Shape,
+    wave: Waveform,
 ) -> Result<(), hound::Error> {
-    let wav_spec = hound::WavSpec {
-        channels: 2,
-        sample_rate: rate,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-    let chan_l = Signal::new(rate, freq, dur, 0.0)
-        .map(shape.func())
-        .map(adjust_volume);
-    let chan_r = Signal::new(rate, freq, dur, phase)
-        .map(shape.func())
-        .map(adjust_volume);
-    let mut writer = hound::WavWriter::create(file, wav_spec)?;
+    let total = (dur * cfg.rate as f32) as u32;
+    let chan_l = cfg.process(
+        Signal::new(cfg.rate, freq, dur, 0.0).map(|(t, dt)| wave.eval(t, dt)),
+        total,
+    );
+    let chan_r = cfg.process(
+        Signal::new(cfg.rate, freq, dur, phase).map(|(t, dt)| wave.eval(t, dt)),
+        total,
+    );
+    let mut writer = hound::WavWriter::create(file, cfg.spec)?;
     for (l, r) in chan_l.zip(chan_r) {
-        writer.write_sample(l)?;
-        writer.write_sample(r)?;
+        write_frame(&mut writer, &cfg.spec, l, r)?;
     }
     Ok(())
 }
 
 fn combo(
     file: &str,
-    rate: u32,
+    cfg: &RenderConfig,
     dur1: f32,
     dur2: f32,
     freq: f32,
     shift: f32,
-    shape: Shape,
+    wave: Waveform,
 ) -> Result<(), hound::Error> {
     assert!(shift > 0.0);
-    let wav_spec = hound::WavSpec {
-        channels: 2,
-        sample_rate: rate,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-    let mut writer = hound::WavWriter::create(file, wav_spec)?;
+    let total = (dur1 * cfg.rate as f32) as u32;
+    let mut writer = hound::WavWriter::create(file, cfg.spec)?;
     for n in 0..(360.0 / shift) as usize {
-        let chan_l = Signal::new(rate, freq, dur1, 0.0)
-            .map(shape.func())
-            .map(adjust_volume);
-        let chan_r = Signal::new(rate, freq, dur1, shift * (n as f32))
-            .map(shape.func())
-            .map(adjust_volume);
+        let chan_l = cfg.process(
+            Signal::new(cfg.rate, freq, dur1, 0.0).map(|(t, dt)| wave.eval(t, dt)),
+            total,
+        );
+        let chan_r = cfg.process(
+            Signal::new(cfg.rate, freq, dur1, shift * (n as f32)).map(|(t, dt)| wave.eval(t, dt)),
+            total,
+        );
         for (l, r) in chan_l.zip(chan_r) {
-            writer.write_sample(l)?;
-            writer.write_sample(r)?;
+            write_frame(&mut writer, &cfg.spec, l, r)?;
         }
-        for s in Silence::new(rate, dur2) {
-            writer.write_sample(s)?;
-            writer.write_sample(s)?;
+        for _ in Silence::new(cfg.spec.sample_rate, dur2) {
+            write_frame(&mut writer, &cfg.spec, 0.0, 0.0)?;
         }
     }
     Ok(())
@@ -195,30 +557,250 @@ fn combo(
 
 fn modulate(
     file: &str,
-    rate: u32,
+    cfg: &RenderConfig,
     dur: f32,
     freq1: f32,
     freq2: f32,
-    shape1: Shape,
-    shape2: Shape,
+    wave1: Waveform,
+    wave2: Waveform,
 ) -> Result<(), hound::Error> {
-    let wav_spec = hound::WavSpec {
-        channels: 2,
-        sample_rate: rate,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
+    let total = (dur * cfg.rate as f32) as u32;
+    let s1 = Signal::new(cfg.rate, freq1, dur, 0.0);
+    let s2 = Signal::new(cfg.rate, freq2, dur, 0.0);
+    let mut writer = hound::WavWriter::create(file, cfg.spec)?;
+    let func = |((t1, dt1), (t2, dt2))| wave1.eval(t1, dt1) * wave2.eval(t2, dt2);
+    let mixed = cfg.process(s1.zip(s2).map(func), total);
+    for s in mixed {
+        write_frame(&mut writer, &cfg.spec, s, s)?;
+    }
+    Ok(())
+}
+
+// Sums an arbitrary number of oscillator channels sample-by-sample; used to
+// mix the partials of a `harmonics` tone. Assumes all channels share the
+// same length, which holds since every partial is built from the same
+// `dur`/`rate`.
+struct Mixer<I> {
+    chans: Vec<I>,
+}
+
+impl<I: Iterator<Item = f32>> Iterator for Mixer<I> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut sum = 0.0;
+        for chan in self.chans.iter_mut() {
+            sum += chan.next()?;
+        }
+        Some(sum)
+    }
+}
+
+// Parses `harmonic:amplitude` pairs such as `1:1.0,2:0.5,3:0.33`.
+fn parse_partials(spec: &str) -> Vec<(u32, f32)> {
+    spec.split(',')
+        .map(|tok| {
+            let tok = tok.trim();
+            let mut parts = tok.splitn(2, ':');
+            let harmonic = parts
+                .next()
+                .unwrap()
+                .trim()
+                .parse::<u32>()
+                .unwrap_or_else(|_| panic!("invalid harmonic number: {}", tok));
+            let amp = parts
+                .next()
+                .unwrap_or_else(|| panic!("missing amplitude for partial: {}", tok))
+                .trim()
+                .parse::<f32>()
+                .unwrap_or_else(|_| panic!("invalid amplitude: {}", tok));
+            (harmonic, amp)
+        }).collect()
+}
+
+fn harmonics(
+    file: &str,
+    cfg: &RenderConfig,
+    dur: f32,
+    freq: f32,
+    partials: Vec<(u32, f32)>,
+) -> Result<(), hound::Error> {
+    assert!(!partials.is_empty());
+    // Partials whose frequency would hit or exceed the sample rate can't be
+    // represented by `Signal`; drop them rather than let `Signal::new`'s
+    // frequency assert panic on one of them.
+    let partials: Vec<(u32, f32)> = partials
+        .into_iter()
+        .filter(|&(harmonic, _)| freq * (harmonic as f32) < cfg.rate as f32)
+        .collect();
+    assert!(!partials.is_empty(), "all partials are at or above the sample rate");
+    let norm: f32 = partials.iter().map(|&(_, amp)| amp.abs()).sum();
+    assert!(norm > 0.0);
+    let total = (dur * cfg.rate as f32) as u32;
+    let chans: Vec<Box<dyn Iterator<Item = f32>>> = partials
+        .into_iter()
+        .map(|(harmonic, amp)| {
+            let osc: Box<dyn Iterator<Item = f32>> = Box::new(
+                Signal::new(cfg.rate, freq * harmonic as f32, dur, 0.0)
+                    .map(move |(t, _dt)| gen_sine(t) * amp),
+            );
+            osc
+        }).collect();
+    let mixed = Mixer { chans }.map(move |x| x / norm);
+    let enveloped = cfg.process(mixed, total);
+    let mut writer = hound::WavWriter::create(file, cfg.spec)?;
+    for s in enveloped {
+        write_frame(&mut writer, &cfg.spec, s, s)?;
+    }
+    Ok(())
+}
+
+// A single event in a parsed melody score: a pitched note, or a rest when
+// `freq` is `None`. Both carry a duration in seconds.
+struct NoteEvent {
+    freq: Option<f32>,
+    dur: f32,
+}
+
+// Converts a note name such as `A4`, `C#5` or `Bb3` (letter, optional `#`/`b`
+// accidental, octave) to a frequency in Hz via equal temperament, using
+// MIDI note numbers (`A4` = 69 = 440 Hz) as the intermediate representation.
+fn parse_note_name(name: &str) -> f32 {
+    let mut chars = name.chars().peekable();
+    let letter = chars
+        .next()
+        .unwrap_or_else(|| panic!("empty note name"))
+        .to_ascii_uppercase();
+    let mut semitone = match letter {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => panic!("invalid note letter: {}", name),
     };
-    let s1 = Signal::new(rate, freq1, dur, 0.0);
-    let s2 = Signal::new(rate, freq2, dur, 0.0);
-    let mut writer = hound::WavWriter::create(file, wav_spec)?;
-    let func = |(x, y)| adjust_volume(shape1.func()(x) * shape2.func()(y));
-    for s in s1.zip(s2).map(func) {
-        writer.write_sample(s)?;
-        writer.write_sample(s)?;
+    match chars.peek() {
+        Some('#') => {
+            semitone += 1;
+            chars.next();
+        }
+        Some('b') => {
+            semitone -= 1;
+            chars.next();
+        }
+        _ => {}
+    }
+    let octave: i32 = chars
+        .collect::<String>()
+        .parse()
+        .unwrap_or_else(|_| panic!("invalid octave in note: {}", name));
+    let midi = (octave + 1) * 12 + semitone;
+    440.0 * 2f32.powf((midi as f32 - 69.0) / 12.0)
+}
+
+// Parses a compact score such as `A4:0.5 C#5:0.25 R:0.5`, where `R` is a
+// rest. When `bpm` is given, the number after `:` is beats instead of
+// seconds and is converted using `60/bpm` seconds per beat.
+fn parse_score(text: &str, bpm: Option<f32>) -> Vec<NoteEvent> {
+    text.split_whitespace()
+        .map(|tok| {
+            let mut parts = tok.splitn(2, ':');
+            let name = parts.next().unwrap();
+            let dur_str = parts
+                .next()
+                .unwrap_or_else(|| panic!("missing duration in token: {}", tok));
+            let mut dur = dur_str
+                .parse::<f32>()
+                .unwrap_or_else(|_| panic!("invalid duration: {}", tok));
+            if let Some(bpm) = bpm {
+                dur *= 60.0 / bpm;
+            }
+            if name.eq_ignore_ascii_case("r") {
+                NoteEvent { freq: None, dur }
+            } else {
+                NoteEvent {
+                    freq: Some(parse_note_name(name)),
+                    dur,
+                }
+            }
+        }).collect()
+}
+
+// Reads the score from `spec` if it names an existing file, otherwise
+// treats `spec` itself as the inline score text.
+fn read_score_source(spec: &str) -> String {
+    std::fs::read_to_string(spec).unwrap_or_else(|_| spec.to_string())
+}
+
+fn melody(
+    file: &str,
+    cfg: &RenderConfig,
+    wave: Waveform,
+    score: Vec<NoteEvent>,
+) -> Result<(), hound::Error> {
+    let mut writer = hound::WavWriter::create(file, cfg.spec)?;
+    for note in score {
+        match note.freq {
+            Some(freq) => {
+                let total = (note.dur * cfg.rate as f32) as u32;
+                let osc = Signal::new(cfg.rate, freq, note.dur, 0.0).map(|(t, dt)| wave.eval(t, dt));
+                let enveloped = cfg.process(osc, total);
+                for s in enveloped {
+                    write_frame(&mut writer, &cfg.spec, s, s)?;
+                }
+            }
+            None => {
+                for _ in Silence::new(cfg.spec.sample_rate, note.dur) {
+                    write_frame(&mut writer, &cfg.spec, 0.0, 0.0)?;
+                }
+            }
+        }
     }
     Ok(())
 }
 
+// ADSR flags shared across the `plain`, `combo`, `modulate`, `harmonics` and
+// `melody` subcommands.
+fn adsr_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("attack")
+            .long("attack")
+            .value_name("SEC")
+            .takes_value(true)
+            .default_value("0")
+            .help("ADSR attack time in Sec"),
+        Arg::with_name("decay")
+            .long("decay")
+            .value_name("SEC")
+            .takes_value(true)
+            .default_value("0")
+            .help("ADSR decay time in Sec"),
+        Arg::with_name("sustain")
+            .long("sustain")
+            .value_name("LEVEL")
+            .takes_value(true)
+            .default_value("1.0")
+            .help("ADSR sustain level in [0,1]"),
+        Arg::with_name("release")
+            .long("release")
+            .value_name("SEC")
+            .takes_value(true)
+            .default_value("0")
+            .help("ADSR release time in Sec"),
+    ]
+}
+
+fn adsr_params(m: &clap::ArgMatches) -> AdsrParams {
+    AdsrParams {
+        attack: value_t!(m.value_of("attack"), f32).unwrap_or_else(|e| e.exit()),
+        decay: value_t!(m.value_of("decay"), f32).unwrap_or_else(|e| e.exit()),
+        sustain: value_t!(m.value_of("sustain"), f32).unwrap_or_else(|e| e.exit()),
+        release: value_t!(m.value_of("release"), f32).unwrap_or_else(|e| e.exit()),
+    }
+}
+
 fn main() {
     let matches = App::new("Signal generator")
         .version(crate_version!())
@@ -230,6 +812,42 @@ fn main() {
                 .takes_value(true)
                 .default_value("44100")
                 .help("Sets a sample rate in Hz"),
+        ).arg(
+            Arg::with_name("filter")
+                .long("filter")
+                .value_name("COEFFS")
+                .takes_value(true)
+                .help("FIR filter coefficients: comma-separated list, or path to a text file"),
+        ).arg(
+            Arg::with_name("channels")
+                .long("channels")
+                .value_name("COUNT")
+                .takes_value(true)
+                .default_value("2")
+                .help("Number of output channels"),
+        ).arg(
+            Arg::with_name("bits")
+                .long("bits")
+                .value_name("BITS")
+                .takes_value(true)
+                .default_value("16")
+                .possible_values(&["8", "16", "24", "32"])
+                .help("Bits per sample"),
+        ).arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .default_value("int")
+                .possible_values(&Format::variants())
+                .case_insensitive(true)
+                .help("Sample representation: int or float"),
+        ).arg(
+            Arg::with_name("out-rate")
+                .long("out-rate")
+                .value_name("SAMPLE_RATE")
+                .takes_value(true)
+                .help("resample the generated signal to this output rate in Hz, band-limited"),
         ).arg(
             Arg::with_name("OUTPUT")
                 .help("name of output file")
@@ -259,7 +877,11 @@ fn main() {
                         .required(true)
                         .possible_values(&Shape::variants())
                         .index(4),
-                ),
+                ).arg(
+                    Arg::with_name("antialias")
+                        .long("antialias")
+                        .help("band-limit saw/square via PolyBLEP to reduce aliasing"),
+                ).args(&adsr_args()),
         ).subcommand(
             SubCommand::with_name("combo")
                 .about("Generates a combo wave")
@@ -289,7 +911,11 @@ fn main() {
                         .required(true)
                         .possible_values(&Shape::variants())
                         .index(5),
-                ),
+                ).arg(
+                    Arg::with_name("antialias")
+                        .long("antialias")
+                        .help("band-limit saw/square via PolyBLEP to reduce aliasing"),
+                ).args(&adsr_args()),
         ).subcommand(
             SubCommand::with_name("modulate")
                 .about("Generates a modulated wave")
@@ -320,32 +946,127 @@ fn main() {
                         .required(true)
                         .possible_values(&Shape::variants())
                         .index(5),
-                ),
+                ).arg(
+                    Arg::with_name("antialias")
+                        .long("antialias")
+                        .help("band-limit saw/square via PolyBLEP to reduce aliasing"),
+                ).args(&adsr_args()),
+        ).subcommand(
+            SubCommand::with_name("harmonics")
+                .about("Generates a tone by additive synthesis of sine partials")
+                .arg(
+                    Arg::with_name("FREQ")
+                        .help("fundamental frequency in Hz")
+                        .required(true)
+                        .index(1),
+                ).arg(
+                    Arg::with_name("DURATION")
+                        .help("signal duration in Sec")
+                        .required(true)
+                        .index(2),
+                ).arg(
+                    Arg::with_name("PARTIALS")
+                        .help("harmonic:amplitude pairs, e.g. 1:1.0,2:0.5,3:0.33")
+                        .required(true)
+                        .index(3),
+                ).args(&adsr_args()),
+        ).subcommand(
+            SubCommand::with_name("melody")
+                .about("Renders a compact note score (inline or from a file) to a WAV")
+                .arg(
+                    Arg::with_name("SCORE")
+                        .help("score text, e.g. \"A4:0.5 C#5:0.25 R:0.5\", or a path to a file")
+                        .required(true)
+                        .index(1),
+                ).arg(
+                    Arg::with_name("SHAPE")
+                        .help("shape of signal")
+                        .required(true)
+                        .possible_values(&Shape::variants())
+                        .index(2),
+                ).arg(
+                    Arg::with_name("bpm")
+                        .long("bpm")
+                        .value_name("BPM")
+                        .takes_value(true)
+                        .help("interpret score durations as beats at this tempo instead of seconds"),
+                ).arg(
+                    Arg::with_name("antialias")
+                        .long("antialias")
+                        .help("band-limit saw/square via PolyBLEP to reduce aliasing"),
+                ).args(&adsr_args()),
         ).get_matches();
 
     let rate = value_t!(matches.value_of("rate"), u32).unwrap_or_else(|e| e.exit());
     let file = matches.value_of("OUTPUT").unwrap();
+    let filter = matches.value_of("filter").map(parse_fir_coeffs);
+    let channels = value_t!(matches.value_of("channels"), u16).unwrap_or_else(|e| e.exit());
+    assert!(channels >= 1, "channel count must be at least 1");
+    let bits = value_t!(matches.value_of("bits"), u16).unwrap_or_else(|e| e.exit());
+    let format = value_t!(matches.value_of("format"), Format).unwrap_or_else(|e| e.exit());
+    match format {
+        Format::Float => assert!(bits == 32, "--format float requires --bits 32"),
+        Format::Int => {}
+    }
+    let out_rate = matches
+        .value_of("out-rate")
+        .map(|_| value_t!(matches.value_of("out-rate"), u32).unwrap_or_else(|e| e.exit()));
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate: out_rate.unwrap_or(rate),
+        bits_per_sample: bits,
+        sample_format: match format {
+            Format::Int => hound::SampleFormat::Int,
+            Format::Float => hound::SampleFormat::Float,
+        },
+    };
 
     if let Some(m) = matches.subcommand_matches("plain") {
         let freq = value_t!(m.value_of("FREQ"), f32).unwrap_or_else(|e| e.exit());
         let dur = value_t!(m.value_of("DURATION"), f32).unwrap_or_else(|e| e.exit());
         let phase = value_t!(m.value_of("PHASE"), f32).unwrap_or_else(|e| e.exit());
         let shape = value_t!(m.value_of("SHAPE"), Shape).unwrap_or_else(|e| e.exit());
-        plain(file, rate, dur, freq, phase, shape).unwrap();
+        let antialias = m.is_present("antialias");
+        let wave = Waveform { shape, antialias };
+        let cfg = RenderConfig { rate, filter, adsr: adsr_params(m), out_rate, spec };
+        plain(file, &cfg, dur, freq, phase, wave).unwrap();
     } else if let Some(m) = matches.subcommand_matches("combo") {
         let freq = value_t!(m.value_of("FREQ"), f32).unwrap_or_else(|e| e.exit());
         let dur = value_t!(m.value_of("DURATION"), f32).unwrap_or_else(|e| e.exit());
         let sil = value_t!(m.value_of("SILENCE"), f32).unwrap_or_else(|e| e.exit());
         let phase = value_t!(m.value_of("PHASE"), f32).unwrap_or_else(|e| e.exit());
         let shape = value_t!(m.value_of("SHAPE"), Shape).unwrap_or_else(|e| e.exit());
-        combo(file, rate, dur, sil, freq, phase, shape).unwrap();
+        let antialias = m.is_present("antialias");
+        let wave = Waveform { shape, antialias };
+        let cfg = RenderConfig { rate, filter, adsr: adsr_params(m), out_rate, spec };
+        combo(file, &cfg, dur, sil, freq, phase, wave).unwrap();
     } else if let Some(m) = matches.subcommand_matches("modulate") {
         let dur = value_t!(m.value_of("DURATION"), f32).unwrap_or_else(|e| e.exit());
         let freq1 = value_t!(m.value_of("FREQ1"), f32).unwrap_or_else(|e| e.exit());
         let freq2 = value_t!(m.value_of("FREQ2"), f32).unwrap_or_else(|e| e.exit());
         let shape1 = value_t!(m.value_of("SHAPE1"), Shape).unwrap_or_else(|e| e.exit());
         let shape2 = value_t!(m.value_of("SHAPE2"), Shape).unwrap_or_else(|e| e.exit());
-        modulate(file, rate, dur, freq1, freq2, shape1, shape2).unwrap();
+        let antialias = m.is_present("antialias");
+        let wave1 = Waveform { shape: shape1, antialias };
+        let wave2 = Waveform { shape: shape2, antialias };
+        let cfg = RenderConfig { rate, filter, adsr: adsr_params(m), out_rate, spec };
+        modulate(file, &cfg, dur, freq1, freq2, wave1, wave2).unwrap();
+    } else if let Some(m) = matches.subcommand_matches("harmonics") {
+        let freq = value_t!(m.value_of("FREQ"), f32).unwrap_or_else(|e| e.exit());
+        let dur = value_t!(m.value_of("DURATION"), f32).unwrap_or_else(|e| e.exit());
+        let partials = parse_partials(m.value_of("PARTIALS").unwrap());
+        let cfg = RenderConfig { rate, filter, adsr: adsr_params(m), out_rate, spec };
+        harmonics(file, &cfg, dur, freq, partials).unwrap();
+    } else if let Some(m) = matches.subcommand_matches("melody") {
+        let shape = value_t!(m.value_of("SHAPE"), Shape).unwrap_or_else(|e| e.exit());
+        let bpm = m
+            .value_of("bpm")
+            .map(|b| b.parse::<f32>().unwrap_or_else(|_| panic!("invalid bpm: {}", b)));
+        let score = parse_score(&read_score_source(m.value_of("SCORE").unwrap()), bpm);
+        let antialias = m.is_present("antialias");
+        let wave = Waveform { shape, antialias };
+        let cfg = RenderConfig { rate, filter, adsr: adsr_params(m), out_rate, spec };
+        melody(file, &cfg, wave, score).unwrap();
     } else {
         Error::with_description("Invalid subcommnad", ErrorKind::InvalidSubcommand).exit()
     }